@@ -0,0 +1,41 @@
+//! Native file dialogs, used when a path flag is given without a value.
+
+use rfd::FileDialog;
+use std::path::PathBuf;
+
+pub fn open_recon() -> Option<PathBuf> {
+    FileDialog::new()
+        .set_title("Open reconstruction")
+        .add_filter("Text files", &["txt"])
+        .add_filter("All files", &["*"])
+        .pick_file()
+}
+
+pub fn open_cost_profile() -> Option<PathBuf> {
+    FileDialog::new()
+        .set_title("Open cost profile")
+        .add_filter("Text files", &["txt"])
+        .add_filter("All files", &["*"])
+        .pick_file()
+}
+
+pub fn save_output() -> Option<PathBuf> {
+    FileDialog::new()
+        .set_title("Save optimized reconstruction")
+        .add_filter("Text files", &["txt"])
+        .set_file_name("reconstruction.txt")
+        .save_file()
+}
+
+/// Resolves a path flag that may be given without a value, in which case the
+/// user is asked to pick one with `dialog`. Exits if they cancel.
+pub fn resolve(arg: &Option<Option<PathBuf>>, dialog: fn() -> Option<PathBuf>) -> Option<PathBuf> {
+    match arg {
+        None => None,
+        Some(Some(path)) => Some(path.clone()),
+        Some(None) => Some(dialog().unwrap_or_else(|| {
+            eprintln!("No file selected.");
+            std::process::exit(1)
+        })),
+    }
+}
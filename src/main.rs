@@ -1,17 +1,39 @@
-use clap::Parser;
+use clap::{ArgGroup, Parser, ValueEnum};
 use cubesim::{parse_scramble, Cube, FaceletCube, Move, MoveVariant, PruningTable, Solver};
 use lazy_static::lazy_static;
-use std::collections::HashSet;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, Ordering::SeqCst};
+use std::sync::OnceLock;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+mod dialogs;
+mod palette;
+mod recon;
+mod table;
+mod trace;
+mod update;
+
+use palette::{paint, Palette, Role};
+use table::TranspositionTable;
+use trace::{Outcome, Trace, TraceNode};
 
 static PRUNING_TABLE_DEPTH: AtomicI32 = AtomicI32::new(0);
 static STICKER_NOTATION: AtomicBool = AtomicBool::new(false);
 static CHEAP_MOVES: AtomicU32 = AtomicU32::new(0);
+static STATUS_TO_STDERR: AtomicBool = AtomicBool::new(false);
+static CUSTOM_NOTATION: OnceLock<HashMap<Reorient, String>> = OnceLock::new();
 
 lazy_static! {
     static ref NAIVE_SOLVER: Solver = make_naive_solver();
+    static ref REORIENT_STATES: Vec<FaceletCube> = Reorient::ALL
+        .iter()
+        .map(|r| FaceletCube::new(3).apply_moves(r.equivalent_rkt_moves()))
+        .collect();
 }
 
 fn make_naive_solver() -> Solver {
@@ -26,19 +48,26 @@ fn make_naive_solver() -> Solver {
         .flat_map(|f| variants.into_iter().map(f))
         .collect();
 
-    let initial_states: Vec<FaceletCube> = Reorient::ALL
-        .iter()
-        .map(|r| FaceletCube::new(3).apply_moves(r.equivalent_rkt_moves()))
-        .collect();
-
-    let pruning_table =
-        PruningTable::new(&initial_states, PRUNING_TABLE_DEPTH.load(SeqCst), &move_set);
+    let pruning_table = PruningTable::new(
+        &REORIENT_STATES,
+        PRUNING_TABLE_DEPTH.load(SeqCst),
+        &move_set,
+    );
 
     Solver::new(move_set, pruning_table)
 }
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
+#[clap(
+    after_help = "Moves may be prefixed with `*` (e.g. `R U *R' U'`) to softly \
+                     prefer reorienting just before them. Each `*` makes a reorient \
+                     at that position 1 ETM cheaper when ranking solutions.\n\n\
+                  After a search, `:notation <xyz|stickers|custom> [ROW]...` shows \
+                  the given rows of the results (or all of them) in another \
+                  notation."
+)]
+#[clap(group(ArgGroup::new("limit").multiple(true)))]
 pub struct Args {
     /// Depth of pruning table (must be at least 2).
     #[clap(short, long, default_value_t = 2)]
@@ -48,6 +77,12 @@ pub struct Args {
     #[clap(short, long)]
     stickers: bool,
 
+    /// File of custom names for reorientations, one per line as a
+    /// reorientation in the same format as --cheap-moves followed by its
+    /// name (e.g. `xy2 Oxy2'`). Shown with `:notation custom`.
+    #[clap(long)]
+    notation_map: Option<PathBuf>,
+
     /// Output all STM-optimal algorithms instead of just the ETM-optimal
     /// subset.
     #[clap(short, long)]
@@ -58,38 +93,157 @@ pub struct Args {
     #[clap(short, long)]
     cheap_moves: Vec<String>,
 
+    /// File listing reorientations that should be considered 1 ETM, in the
+    /// same format as --cheap-moves. Opens a file dialog if no path is given.
+    #[clap(long)]
+    cost_profile: Option<Option<PathBuf>>,
+
     /// Maximum depth to search.
     #[clap(short, long, default_value_t = 3)]
     max_depth: usize,
+
+    /// Keep raising the number of reorients until a solution is found or
+    /// the time or node limit is reached, ignoring --max-depth. Requires one
+    /// of those limits.
+    #[clap(long, requires = "limit")]
+    auto: bool,
+
+    /// Give up searching after this many seconds.
+    #[clap(long, group = "limit", value_parser = parse_seconds)]
+    time_limit: Option<Duration>,
+
+    /// Give up searching after visiting this many nodes.
+    #[clap(long, group = "limit")]
+    node_limit: Option<u64>,
+
+    /// Number of search results to memoize per algorithm, each holding at most
+    /// 64 solutions. When full, results for larger subtrees are kept. 0
+    /// disables memoization.
+    #[clap(long, default_value_t = 1 << 18)]
+    table_size: usize,
+
+    /// Print the explored search tree down to this depth. Deeper nodes are
+    /// collapsed into a count.
+    #[clap(long)]
+    inspect_depth: Option<usize>,
+
+    /// Colors to highlight output with. Colors are disabled when output is
+    /// not a terminal or `NO_COLOR` is set.
+    #[clap(long, value_enum, default_value = "default")]
+    palette: Palette,
+
+    /// Check GitHub for a newer release on startup.
+    #[clap(long)]
+    check_updates: bool,
+
+    /// Optimize each phase of a reconstruction file instead of reading
+    /// algorithms interactively. Opens a file dialog if no path is given.
+    #[clap(long)]
+    recon: Option<Option<PathBuf>>,
+
+    /// File to write the optimized reconstruction to, instead of standard
+    /// output. Opens a file dialog if no path is given.
+    #[clap(short, long, requires = "recon")]
+    output: Option<Option<PathBuf>>,
+}
+impl Args {
+    /// Returns the maximum number of reorients to search.
+    fn search_depth(&self) -> usize {
+        if self.auto {
+            usize::MAX
+        } else {
+            self.max_depth
+        }
+    }
+}
+
+/// Parses a finite, non-negative number of seconds.
+fn parse_seconds(s: &str) -> Result<Duration, String> {
+    let secs: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    Duration::try_from_secs_f64(secs)
+        .map_err(|_| "must be a finite, non-negative number of seconds".to_string())
 }
 
 fn main() {
     let args = Args::parse();
 
-    let cheap_move_set: HashSet<_> = args
-        .cheap_moves
-        .into_iter()
-        .map(|s| format!(" O{} ", s))
-        .collect();
+    // Check for updates in the background. The result is shown whenever it
+    // arrives, without ever waiting for it.
+    let mut update_check = args
+        .check_updates
+        .then(|| std::thread::spawn(update::check));
+
+    // Ask for every file up front, so that nothing is lost if a dialog is
+    // cancelled.
+    let cost_profile_path = dialogs::resolve(&args.cost_profile, dialogs::open_cost_profile);
+    let recon_path = dialogs::resolve(&args.recon, dialogs::open_recon);
+    let output_path = dialogs::resolve(&args.output, dialogs::save_output);
+
+    let mut cheap_moves = args.cheap_moves.clone();
+    if let Some(path) = &cost_profile_path {
+        let text = read_or_exit(path);
+        cheap_moves.extend(parse_cost_profile(&text));
+    }
+    let cheap_move_set: HashSet<_> = cheap_moves.iter().map(|s| format!("O{}", s)).collect();
     let mut cheap_move_set_mask = 0;
     for (i, r) in Reorient::ALL.iter().enumerate() {
-        if cheap_move_set.contains(&r.to_string()) {
+        if !r.is_none() && cheap_move_set.contains(&r.name(Notation::Xyz)) {
             cheap_move_set_mask |= 1 << i;
         }
     }
     CHEAP_MOVES.store(cheap_move_set_mask, SeqCst);
 
+    if let Some(path) = &args.notation_map {
+        let text = read_or_exit(path);
+        let names = parse_notation_map(&text).unwrap_or_else(|e| {
+            eprintln!("Invalid notation map {}: {}", path.display(), e);
+            std::process::exit(1)
+        });
+        CUSTOM_NOTATION.set(names).unwrap();
+    }
+
     PRUNING_TABLE_DEPTH.store(args.depth as i32, SeqCst);
     STICKER_NOTATION.store(args.stickers, SeqCst);
+    palette::set(args.palette);
+    // Standard output is reserved for the optimized reconstruction.
+    STATUS_TO_STDERR.store(recon_path.is_some() && output_path.is_none(), SeqCst);
 
-    println!("Initializing pruning table to depth {} ...", args.depth);
+    print_status(&format!(
+        "Initializing pruning table to depth {} ...",
+        args.depth
+    ));
 
     let _ = &*NAIVE_SOLVER;
 
-    println!("Ready!");
-    println!();
+    print_status("Ready!");
+    print_status("");
+
+    show_update_banner(&mut update_check);
+
+    if let Some(recon_path) = &recon_path {
+        let text = read_or_exit(recon_path);
+        let optimized = recon::optimize(&text, &args);
+        match &output_path {
+            Some(output_path) => {
+                if let Err(e) = std::fs::write(output_path, optimized) {
+                    eprintln!("Unable to write {}: {}", output_path.display(), e);
+                    std::process::exit(1)
+                }
+            }
+            None => print!("{}", palette::paint_recon(&optimized)),
+        }
+        show_update_banner(&mut update_check);
+        return;
+    }
+
+    // Solutions from the last search, each with the notation it is shown in.
+    let mut rows: Vec<(Vec<Step>, Notation)> = vec![];
+    // Reused across searches to avoid reallocating it for every algorithm.
+    let mut table = TranspositionTable::new(args.table_size);
 
     loop {
+        show_update_banner(&mut update_check);
+
         let mut alg_string = String::new();
 
         print!("Enter rotationless algorithm: ");
@@ -103,63 +257,358 @@ fn main() {
             _ => (),
         }
 
-        let alg = parse_scramble(alg_string);
+        if let Some(command) = alg_string.trim().strip_prefix(':') {
+            run_command(command, &mut rows);
+            println!();
+            continue;
+        }
+
+        let (alg, preferences) = match parse_annotated_alg(&alg_string) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                println!(
+                    "{}",
+                    paint(Role::Warning, &format!("Invalid algorithm: {e}"))
+                );
+                println!();
+                continue;
+            }
+        };
 
-        let (reorient_count, mut solutions) = iddfs(&alg, args.max_depth);
+        let mut budget = Budget::new(args.node_limit, args.time_limit);
+        table.clear();
+        let (min_reorients, mut solutions) = iddfs(
+            &alg,
+            &preferences,
+            args.search_depth(),
+            args.inspect_depth,
+            &mut budget,
+            &mut table,
+        );
         let solution_count = solutions.len();
-        if solution_count == 0 {
-            println!("No solutions?");
-        } else {
-            let stm = alg.len() + reorient_count;
-            println!(
-                "Found {solution_count} solutions with {reorient_count} reorients ({stm} STM)."
+        rows.clear();
+        if budget.is_exhausted() {
+            let message = format!(
+                "Gave up: out of budget. Every solution needs at least \
+                 {min_reorients} reorients."
             );
+            println!("{}", paint(Role::Warning, &message));
+        } else if solution_count == 0 {
+            println!("{}", paint(Role::Warning, "No solutions?"));
+        } else {
+            // Simplification may have changed the counts from the search.
+            let reorients =
+                display_range(solutions.iter().map(|(_cost, steps)| reorient_count(steps)));
+            let stm = display_range(solutions.iter().map(|(_cost, steps)| steps.len()));
+            let summary =
+                format!("Found {solution_count} solutions with {reorients} reorients ({stm} STM).");
+            println!("{}", paint(Role::Summary, &summary));
             if !args.all {
-                let min_cost = *solutions.iter().map(|(cost, _string)| cost).min().unwrap();
-                solutions.retain(|(cost, _string)| *cost == min_cost);
+                let min_cost = *solutions.iter().map(|(cost, _steps)| cost).min().unwrap();
+                solutions.retain(|(cost, _steps)| *cost == min_cost);
                 let good_solution_count = solutions.len();
-                println!("{good_solution_count} of them add only {min_cost} ETM.");
-            }
-            for (_cost, string) in solutions {
-                println!("{}", string);
+                let (weighted_cost, etm) = min_cost;
+                let change = if etm < 0 {
+                    format!("save {} ETM", -etm)
+                } else {
+                    format!("add only {etm} ETM")
+                };
+                let summary = if preferences.iter().any(|&p| p > 0) {
+                    format!(
+                        "{good_solution_count} of them {change} \
+                         (weighted cost {weighted_cost})."
+                    )
+                } else {
+                    format!("{good_solution_count} of them {change}.")
+                };
+                println!("{}", paint(Role::Summary, &summary));
             }
+            rows = solutions
+                .into_iter()
+                .map(|(_cost, steps)| (steps, Notation::global()))
+                .collect();
+            print_rows(&rows);
+        }
+        let stats = format!(
+            "Searched {} nodes in {:.2?}.",
+            budget.nodes,
+            budget.start.elapsed(),
+        );
+        println!("{}", paint(Role::Info, &stats));
+        if table.capacity() > 0 {
+            let table_stats = format!(
+                "Transposition table: {} hits, {} misses, {} evictions, {} rejections, \
+                 {} oversized, {} of {} slots used.",
+                table.stats.hits,
+                table.stats.misses,
+                table.stats.evictions,
+                table.stats.rejections,
+                table.stats.oversized,
+                table.len(),
+                table.capacity(),
+            );
+            println!("{}", paint(Role::Info, &table_stats));
         }
         println!();
     }
 }
 
-fn iddfs(moves: &[Move], max_depth: usize) -> (usize, Vec<(usize, String)>) {
+/// Reads a file given on the command line, exiting if it can't be read.
+fn read_or_exit(path: &Path) -> String {
+    std::fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Unable to read {}: {}", path.display(), e);
+        std::process::exit(1)
+    })
+}
+
+/// Prints a line of progress or other text that is not a result.
+fn print_status(text: &str) {
+    if STATUS_TO_STDERR.load(SeqCst) {
+        eprintln!("{}", text);
+    } else {
+        println!("{}", text);
+    }
+}
+
+/// Shows the result of the update check if it has finished, without waiting
+/// for it. The banner is shown at most once.
+fn show_update_banner(update_check: &mut Option<JoinHandle<Option<update::Update>>>) {
+    if !update_check
+        .as_ref()
+        .is_some_and(|handle| handle.is_finished())
+    {
+        return;
+    }
+    let update = update_check
+        .take()
+        .and_then(|handle| handle.join().ok().flatten());
+    if let Some(update) = update {
+        let banner = format!(
+            "A new version of RocKeT is available: v{} (you have v{}).",
+            update.version,
+            env!("CARGO_PKG_VERSION"),
+        );
+        print_status(&paint(Role::Summary, &banner));
+        print_status(&format!("Changelog: {}", update.changelog_url));
+        print_status("");
+    }
+}
+
+/// Handles a command entered at the prompt after a leading `:`.
+fn run_command(command: &str, rows: &mut [(Vec<Step>, Notation)]) {
+    let mut words = command.split_whitespace();
+    match words.next() {
+        Some("notation") => {
+            let notation = match words.next().map(|w| Notation::from_str(w, true)) {
+                Some(Ok(notation)) => notation,
+                _ => {
+                    let usage = "Usage: :notation <xyz|stickers|custom> [ROW]...";
+                    println!("{}", paint(Role::Warning, usage));
+                    return;
+                }
+            };
+            let row_numbers: Vec<usize> = match words.map(|w| w.parse()).collect() {
+                Ok(row_numbers) => row_numbers,
+                Err(e) => {
+                    println!("{}", paint(Role::Warning, &format!("Invalid row: {e}")));
+                    return;
+                }
+            };
+            if let Some(&n) = row_numbers.iter().find(|&&n| n == 0 || n > rows.len()) {
+                let message = format!("No row {n}; there are {} rows.", rows.len());
+                println!("{}", paint(Role::Warning, &message));
+                return;
+            }
+            if notation == Notation::Custom && CUSTOM_NOTATION.get().is_none() {
+                let message = "No --notation-map given; showing rotations instead.";
+                println!("{}", paint(Role::Warning, message));
+            }
+
+            if row_numbers.is_empty() {
+                rows.iter_mut().for_each(|row| row.1 = notation);
+            } else {
+                row_numbers.iter().for_each(|&n| rows[n - 1].1 = notation);
+            }
+            print_rows(rows);
+        }
+        _ => println!("{}", paint(Role::Warning, "Unknown command.")),
+    }
+}
+
+/// Prints solutions numbered from 1, as referred to by `:notation`.
+fn print_rows(rows: &[(Vec<Step>, Notation)]) {
+    for (n, (steps, notation)) in rows.iter().enumerate() {
+        let highlighted: Vec<String> = steps
+            .iter()
+            .map(|step| match step {
+                Step::Move(mv) => paint(Role::Move, &display_move(*mv)),
+                Step::Reorient(reorient) => paint(Role::Reorient, &reorient.name(*notation)),
+            })
+            .collect();
+        println!("{}) {}", n + 1, highlighted.join(" "));
+    }
+}
+
+/// Parses a notation map: lines of a reorientation in the same format as
+/// `--cheap-moves` followed by its custom name, with `//` starting a comment.
+fn parse_notation_map(text: &str) -> Result<HashMap<Reorient, String>, String> {
+    let mut names = HashMap::new();
+    for line in text.lines() {
+        let line = line.split("//").next().unwrap_or_default();
+        let mut words = line.split_whitespace();
+        let (Some(reorient), Some(name), None) = (words.next(), words.next(), words.next()) else {
+            if line.trim().is_empty() {
+                continue;
+            }
+            return Err(format!("expected a reorientation and a name: {line:?}"));
+        };
+        let xyz_name = format!("O{reorient}");
+        let reorient = Reorient::ALL
+            .iter()
+            .find(|r| !r.is_none() && r.name(Notation::Xyz) == xyz_name)
+            .ok_or_else(|| format!("unknown reorientation {reorient:?}"))?;
+        names.insert(*reorient, name.to_string());
+    }
+    Ok(names)
+}
+
+/// Parses a cost profile: whitespace-separated reorientations in the same
+/// format as `--cheap-moves`, with `//` starting a comment.
+fn parse_cost_profile(text: &str) -> Vec<String> {
+    text.lines()
+        .flat_map(|line| {
+            line.split("//")
+                .next()
+                .unwrap_or_default()
+                .split_whitespace()
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// Parses an algorithm in which each move may be prefixed by one or more `*`
+/// to mark the position just before it as a preferred place to reorient.
+///
+/// Returns the moves along with the preference weight of each gap between
+/// consecutive moves (the number of `*` on the move after the gap).
+fn parse_annotated_alg(s: &str) -> Result<(Vec<Move>, Vec<usize>), String> {
+    let mut moves = String::new();
+    let mut preferences = vec![];
+    for (i, token) in s.split_whitespace().enumerate() {
+        let mv = token.trim_start_matches('*');
+        let stars = token.len() - mv.len();
+        if mv.is_empty() {
+            return Err(format!("`{token}` must be attached to a move, as in `*R`"));
+        }
+        if !is_supported_move(mv) {
+            return Err(format!("unsupported move `{mv}`"));
+        }
+        if i == 0 {
+            if stars > 0 {
+                return Err("the first move cannot be marked with `*`, \
+                            because there is no gap before it"
+                    .to_string());
+            }
+        } else {
+            preferences.push(stars);
+        }
+        moves += mv;
+        moves += " ";
+    }
+    Ok((parse_scramble(moves), preferences))
+}
+
+/// Returns whether `parse_scramble` understands `token` as a move on a 3x3x3
+/// that can be displayed again.
+fn is_supported_move(token: &str) -> bool {
+    let base = ["2'", "2", "'"]
+        .iter()
+        .find_map(|suffix| token.strip_suffix(suffix))
+        .unwrap_or(token);
+    // No explicit width, because `parse_scramble` would read the `2` in
+    // `2Rw'` as a double turn.
+    matches!(
+        base,
+        "U" | "L"
+            | "F"
+            | "R"
+            | "B"
+            | "D"
+            | "Uw"
+            | "Lw"
+            | "Fw"
+            | "Rw"
+            | "Bw"
+            | "Dw"
+            | "x"
+            | "y"
+            | "z"
+    )
+}
+
+/// Searches with increasing numbers of reorients. If `inspect_depth` is given,
+/// the explored tree of each iteration is printed down to that depth.
+///
+/// If the budget runs out, returns no solutions along with the number of
+/// reorients that was being searched, which is a lower bound on the number
+/// needed.
+fn iddfs(
+    moves: &[Move],
+    preferences: &[usize],
+    max_depth: usize,
+    inspect_depth: Option<usize>,
+    budget: &mut Budget,
+    table: &mut TranspositionTable,
+) -> (usize, Vec<(Cost, Vec<Step>)>) {
     if moves.len() <= 1 {
         return (
             0,
-            vec![(
-                0,
-                moves.first().copied().map(display_move).unwrap_or_default(),
-            )],
+            vec![((0, 0), moves.iter().copied().map(Step::Move).collect())],
         );
     }
 
-    for max_reorients in 0..std::cmp::min(moves.len(), max_depth + 1) {
-        println!("Searching solutions with {} reorients", max_reorients);
-        let ret = dfs(&FaceletCube::new(3), moves, max_reorients);
+    for max_reorients in 0..std::cmp::min(moves.len(), max_depth.saturating_add(1)) {
+        let progress = format!("Searching solutions with {} reorients", max_reorients);
+        print_status(&paint(Role::Info, &progress));
+        let mut trace = inspect_depth.map(|_| vec![]);
+        let ret = dfs(
+            &FaceletCube::new(3),
+            moves,
+            max_reorients,
+            budget,
+            table,
+            trace
+                .as_mut()
+                .zip(inspect_depth)
+                .map(|(nodes, depth_left)| Trace::Record { nodes, depth_left }),
+        );
+        if let Some(trace) = trace {
+            trace::print_tree(&trace);
+        }
+        if budget.is_exhausted() {
+            return (max_reorients, vec![]);
+        }
         if !ret.is_empty() {
-            let solutions = ret
-                .into_iter()
-                .map(|solution| {
-                    // Solutions are reversed, because reasons.
-                    let solution_iter = solution.iter().rev();
-
-                    let mut return_string = display_move(moves[0]);
-                    for (reorient, &mv) in solution_iter.zip(&moves[1..]) {
-                        return_string += &reorient.to_string();
-                        return_string += &display_move(mv);
-                    }
+            let mut solutions: Vec<(Cost, Vec<Step>)> = vec![];
+            let mut indices: HashMap<Vec<Step>, usize> = HashMap::new();
+            for mut solution in ret {
+                // Solutions are reversed, because reasons.
+                solution.reverse();
 
-                    let cost = solution.iter().map(|r| r.cost()).sum();
-
-                    (cost, return_string)
-                })
-                .collect();
+                // Simplification may make distinct solutions identical, so
+                // keep only the cheapest copy.
+                let (cost, steps) = simplify(moves, preferences, &solution);
+                match indices.entry(steps) {
+                    Entry::Occupied(e) => {
+                        let existing_cost = &mut solutions[*e.get()].0;
+                        *existing_cost = std::cmp::min(*existing_cost, cost);
+                    }
+                    Entry::Vacant(e) => {
+                        solutions.push((cost, e.key().clone()));
+                        e.insert(solutions.len() - 1);
+                    }
+                }
+            }
             return (max_reorients, solutions);
         }
     }
@@ -167,22 +616,118 @@ fn iddfs(moves: &[Move], max_depth: usize) -> (usize, Vec<(usize, String)>) {
     (0, vec![])
 }
 
-fn dfs(state: &FaceletCube, moves: &[Move], max_reorients: usize) -> Vec<Solution> {
-    if moves.len() <= 1 || max_reorients == 0 {
+/// Interleaves the reorientations of `solution` with `moves`, then merges each
+/// reorientation with any adjacent rotations or other reorientations into a
+/// single equivalent reorientation (or none at all, if they cancel).
+///
+/// Returns the cost of the simplified solution along with its steps. A merged
+/// reorientation uses the strongest preference among the gaps it spans, and
+/// the rotations of `moves` it absorbs no longer count as 1 ETM each.
+fn simplify(moves: &[Move], preferences: &[usize], solution: &Solution) -> (Cost, Vec<Step>) {
+    let mut steps = vec![];
+    let mut cost = (0, 0);
+
+    // Rotations and reorientations not yet emitted, as equivalent RKT moves.
+    let mut pending_rotations: Vec<Move> = vec![];
+    // Number of those that are rotations from `moves`.
+    let mut pending_alg_rotations = 0;
+    let mut pending_has_reorient = false;
+    let mut pending_preference = 0;
+
+    let reorients = std::iter::once(Reorient::None).chain(solution.iter().copied());
+    let gap_preferences = std::iter::once(0).chain(preferences.iter().copied());
+    for ((reorient, preference), &mv) in reorients.zip(gap_preferences).zip(moves) {
+        if !reorient.is_none() {
+            pending_rotations.extend_from_slice(reorient.equivalent_rkt_moves());
+            pending_has_reorient = true;
+        }
+        pending_preference = std::cmp::max(pending_preference, preference);
+
+        if is_rotation(mv) {
+            pending_rotations.push(mv);
+            pending_alg_rotations += 1;
+            continue;
+        }
+
+        if pending_has_reorient {
+            // Collapse everything pending into a single reorientation.
+            let merged = Reorient::from_rkt_moves(&pending_rotations);
+            if !merged.is_none() {
+                cost.0 += merged.weighted_cost(pending_preference) as isize;
+                cost.1 += merged.cost() as isize;
+                steps.push(Step::Reorient(merged));
+            }
+            cost.0 -= pending_alg_rotations;
+            cost.1 -= pending_alg_rotations;
+        } else {
+            // Leave rotations from the original algorithm untouched.
+            steps.extend(pending_rotations.iter().map(|&rot| Step::Move(rot)));
+        }
+        steps.push(Step::Move(mv));
+
+        pending_rotations.clear();
+        pending_alg_rotations = 0;
+        pending_has_reorient = false;
+        pending_preference = 0;
+    }
+    // Trailing rotations from the original algorithm.
+    steps.extend(pending_rotations.iter().map(|&rot| Step::Move(rot)));
+
+    (cost, steps)
+}
+
+/// Searches for solutions, recording the explored tree into `trace` if it is
+/// given. Results of expanded nodes are memoized in `table`.
+fn dfs(
+    state: &FaceletCube,
+    moves: &[Move],
+    max_reorients: usize,
+    budget: &mut Budget,
+    table: &mut TranspositionTable,
+    mut trace: Option<Trace<'_>>,
+) -> Vec<Solution> {
+    if !budget.visit() {
+        return vec![];
+    }
+
+    let mut node = match &mut trace {
+        Some(Trace::Record { .. }) => Some(TraceNode::new(state, moves.len(), max_reorients)),
+        Some(Trace::Count(count)) => {
+            **count += 1;
+            None
+        }
+        None => None,
+    };
+
+    let ret = if moves.len() <= 1 || max_reorients == 0 {
         // No more reorients allowed! Are we already solved?
         let end_result = state.apply_moves(moves);
-        if NAIVE_SOLVER.lower_bound(&end_result) <= 1 {
+        let lower_bound = NAIVE_SOLVER.lower_bound(&end_result);
+        if lower_bound <= 1 {
             // Success!
+            set_outcome(&mut node, Outcome::Solved);
             vec![vec![Reorient::None; moves.len().saturating_sub(1)]]
         } else {
             // Fail!
+            set_outcome(&mut node, Outcome::Unsolved { lower_bound });
             vec![]
         }
     } else if NAIVE_SOLVER.lower_bound(state) as usize > moves.len() + 1 {
         // Fail!
+        set_outcome(&mut node, Outcome::Pruned);
         vec![]
+    } else if let Some(ret) = table.get(state, moves.len(), max_reorients) {
+        // Seen it before!
+        set_outcome(
+            &mut node,
+            Outcome::Cached {
+                solutions: ret.len(),
+            },
+        );
+        ret
     } else {
         let mut ret = vec![];
+        let mut children = vec![];
 
         // Try not reorienting right now.
         let new_state = state.apply_move(moves[0]);
@@ -190,11 +735,26 @@ fn dfs(state: &FaceletCube, moves: &[Move], max_reorients: usize) -> Vec<Solutio
         // Try every possible reorient, including the null reorient.
         for &reorient in Reorient::ALL {
             let remaining_reorients = max_reorients - 1 + reorient.is_none() as usize;
+            let recorded_children = children.len();
+            let child_trace = match (&mut trace, &mut node) {
+                (Some(Trace::Record { depth_left: 0, .. }), Some(node)) => {
+                    Some(Trace::Count(&mut node.hidden_descendants))
+                }
+                (Some(Trace::Record { depth_left, .. }), Some(_)) => Some(Trace::Record {
+                    nodes: &mut children,
+                    depth_left: *depth_left - 1,
+                }),
+                (Some(Trace::Count(count)), _) => Some(Trace::Count(count)),
+                _ => None,
+            };
             ret.extend(
                 dfs(
                     &new_state.apply_moves(reorient.equivalent_rkt_moves()),
                     &moves[1..],
                     remaining_reorients,
+                    budget,
+                    table,
+                    child_trace,
                 )
                 .into_iter()
                 .map(|mut solution| {
@@ -202,15 +762,117 @@ fn dfs(state: &FaceletCube, moves: &[Move], max_reorients: usize) -> Vec<Solutio
                     solution
                 }),
             );
+            // The child is not recorded if the budget ran out before it.
+            if children.len() > recorded_children {
+                children.last_mut().unwrap().reorient = reorient;
+            }
         }
 
+        if let Some(node) = &mut node {
+            node.outcome = Outcome::Expanded {
+                solutions: ret.len(),
+            };
+            node.children = children;
+        }
+        // Results are incomplete if the budget ran out partway through.
+        if !budget.is_exhausted() {
+            table.insert(state, moves.len(), max_reorients, &ret);
+        }
         ret
+    };
+
+    if let (Some(Trace::Record { nodes, .. }), Some(node)) = (trace, node) {
+        nodes.push(node);
+    }
+    ret
+}
+
+fn set_outcome(node: &mut Option<TraceNode>, outcome: Outcome) {
+    if let Some(node) = node {
+        node.outcome = outcome;
+    }
+}
+
+/// Limits on how much work a search may do.
+#[derive(Debug, Clone)]
+pub struct Budget {
+    pub start: Instant,
+    pub deadline: Option<Instant>,
+    pub node_limit: Option<u64>,
+    /// Number of nodes visited so far.
+    pub nodes: u64,
+    exhausted: bool,
+}
+impl Budget {
+    pub fn new(node_limit: Option<u64>, time_limit: Option<Duration>) -> Self {
+        let start = Instant::now();
+        Self {
+            start,
+            // A limit too far in the future to represent is no limit at all.
+            deadline: time_limit.and_then(|limit| start.checked_add(limit)),
+            node_limit,
+            nodes: 0,
+            exhausted: false,
+        }
+    }
+
+    /// Counts a visited node. Returns `false` if the budget is exhausted, in
+    /// which case the node should not be searched.
+    pub fn visit(&mut self) -> bool {
+        let out_of_nodes = self.node_limit.is_some_and(|limit| self.nodes >= limit);
+        let out_of_time = self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline);
+        if out_of_nodes || out_of_time {
+            self.exhausted = true;
+            return false;
+        }
+        self.nodes += 1;
+        true
+    }
+
+    /// Returns whether a node was refused because the budget ran out.
+    pub fn is_exhausted(&self) -> bool {
+        self.exhausted
     }
 }
 
 /// Reorientations between each move.
 pub type Solution = Vec<Reorient>;
 
+/// Weighted cost and ETM that a solution adds to the original algorithm, in
+/// that order, so that solutions sort by weighted cost first. Negative if
+/// reorientations cancel rotations of the algorithm.
+pub type Cost = (isize, isize);
+
+/// Notation for reorientations.
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Notation {
+    /// Rotations, such as `Oxy`.
+    Xyz,
+    /// 23I sticker notation, such as `23I:DBL`.
+    Stickers,
+    /// Names from `--notation-map`, falling back to rotations.
+    Custom,
+}
+impl Notation {
+    /// Returns the notation selected on the command line.
+    pub fn global() -> Self {
+        if STICKER_NOTATION.load(SeqCst) {
+            Self::Stickers
+        } else {
+            Self::Xyz
+        }
+    }
+}
+
+/// Move or reorientation in a solution.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Step {
+    Move(Move),
+    Reorient(Reorient),
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Reorient {
     None = 0,
@@ -244,39 +906,9 @@ pub enum Reorient {
 }
 impl fmt::Display for Reorient {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        use Reorient::*;
-
-        let s = STICKER_NOTATION.load(SeqCst);
-
         match self {
-            None => write!(f, " "),
-
-            R => write!(f, " {} ", if s { "23I:L" } else { "Ox" }),
-            L => write!(f, " {} ", if s { "23I:R" } else { "Ox'" }),
-            U => write!(f, " {} ", if s { "23I:D" } else { "Oy" }),
-            D => write!(f, " {} ", if s { "23I:U" } else { "Oy'" }),
-            F => write!(f, " {} ", if s { "23I:B" } else { "Oz" }),
-            B => write!(f, " {} ", if s { "23I:F" } else { "Oz'" }),
-
-            R2 => write!(f, " {} ", if s { "23I:R2" } else { "Ox2" }),
-            U2 => write!(f, " {} ", if s { "23I:U2" } else { "Oy2" }),
-            F2 => write!(f, " {} ", if s { "23I:F2" } else { "Oz2" }),
-
-            UF => write!(f, " {} ", if s { "23I:UF" } else { "Oxy2" }),
-            UR => write!(f, " {} ", if s { "23I:UR" } else { "Ozx2" }),
-            FR => write!(f, " {} ", if s { "23I:FR" } else { "Oyz2" }),
-            DF => write!(f, " {} ", if s { "23I:DF" } else { "Oxz2" }),
-            UL => write!(f, " {} ", if s { "23I:UL" } else { "Ozy2" }),
-            BR => write!(f, " {} ", if s { "23I:BR" } else { "Oyx2" }),
-
-            UFR => write!(f, " {} ", if s { "23I:DBL" } else { "Oxy" }),
-            DBL => write!(f, " {} ", if s { "23I:UFR" } else { "Oy'x'" }),
-            UFL => write!(f, " {} ", if s { "23I:DBR" } else { "Ozy" }),
-            DBR => write!(f, " {} ", if s { "23I:UFL" } else { "Oxy'" }),
-            DFR => write!(f, " {} ", if s { "23I:UBL" } else { "Oxz" }),
-            UBL => write!(f, " {} ", if s { "23I:DFR" } else { "Oyz'" }),
-            UBR => write!(f, " {} ", if s { "23I:DFL" } else { "Oyx" }),
-            DFL => write!(f, " {} ", if s { "23I:UBR" } else { "Ozx'" }),
+            Self::None => write!(f, " "),
+            _ => write!(f, " {} ", self.name(Notation::global())),
         }
     }
 }
@@ -324,6 +956,13 @@ impl Reorient {
         }
     }
 
+    /// Returns the cost of the reorientation at a position annotated with
+    /// preference weight `preference`. Each unit of preference makes a
+    /// reorient at that position 1 ETM cheaper, down to a minimum of 0.
+    pub fn weighted_cost(self, preference: usize) -> usize {
+        self.cost().saturating_sub(preference)
+    }
+
     pub fn equivalent_rkt_moves(self) -> &'static [Move] {
         use Move::{X, Y, Z};
         use MoveVariant::*;
@@ -361,11 +1000,140 @@ impl Reorient {
         }
     }
 
+    /// Returns the reorientation equivalent to a sequence of RKT rotations.
+    pub fn from_rkt_moves(moves: &[Move]) -> Self {
+        let state = FaceletCube::new(3).apply_moves(moves);
+        Reorient::ALL
+            .iter()
+            .zip(REORIENT_STATES.iter())
+            .find(|(_, reorient_state)| **reorient_state == state)
+            .map(|(&reorient, _)| reorient)
+            .expect("every rotation is equivalent to a reorientation")
+    }
+
+    /// Returns the name of the reorientation in `notation`, which is empty
+    /// for the null reorientation.
+    pub fn name(self, notation: Notation) -> String {
+        match notation {
+            Notation::Xyz => self.xyz_name().to_string(),
+            Notation::Stickers => self.sticker_name().to_string(),
+            Notation::Custom => CUSTOM_NOTATION
+                .get()
+                .and_then(|names| names.get(&self))
+                .cloned()
+                .unwrap_or_else(|| self.xyz_name().to_string()),
+        }
+    }
+
+    fn xyz_name(self) -> &'static str {
+        use Reorient::*;
+
+        match self {
+            None => "",
+
+            R => "Ox",
+            L => "Ox'",
+            U => "Oy",
+            D => "Oy'",
+            F => "Oz",
+            B => "Oz'",
+
+            R2 => "Ox2",
+            U2 => "Oy2",
+            F2 => "Oz2",
+
+            UF => "Oxy2",
+            UR => "Ozx2",
+            FR => "Oyz2",
+            DF => "Oxz2",
+            UL => "Ozy2",
+            BR => "Oyx2",
+
+            UFR => "Oxy",
+            DBL => "Oy'x'",
+            UFL => "Ozy",
+            DBR => "Oxy'",
+            DFR => "Oxz",
+            UBL => "Oyz'",
+            UBR => "Oyx",
+            DFL => "Ozx'",
+        }
+    }
+
+    fn sticker_name(self) -> &'static str {
+        use Reorient::*;
+
+        match self {
+            None => "",
+
+            R => "23I:L",
+            L => "23I:R",
+            U => "23I:D",
+            D => "23I:U",
+            F => "23I:B",
+            B => "23I:F",
+
+            R2 => "23I:R2",
+            U2 => "23I:U2",
+            F2 => "23I:F2",
+
+            UF => "23I:UF",
+            UR => "23I:UR",
+            FR => "23I:FR",
+            DF => "23I:DF",
+            UL => "23I:UL",
+            BR => "23I:BR",
+
+            UFR => "23I:DBL",
+            DBL => "23I:UFR",
+            UFL => "23I:DBR",
+            DBR => "23I:UFL",
+            DFR => "23I:UBL",
+            UBL => "23I:DFR",
+            UBR => "23I:DFL",
+            DFL => "23I:UBR",
+        }
+    }
+
     pub fn is_none(self) -> bool {
         self == Self::None
     }
 }
 
+pub fn display_steps(steps: &[Step], notation: Notation) -> String {
+    steps
+        .iter()
+        .map(|step| match step {
+            Step::Move(mv) => display_move(*mv),
+            Step::Reorient(reorient) => reorient.name(notation),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+pub fn reorient_count(steps: &[Step]) -> usize {
+    steps
+        .iter()
+        .filter(|step| matches!(step, Step::Reorient(_)))
+        .count()
+}
+
+/// Displays the range of `values`, such as `3` or `3-4`.
+pub fn display_range(values: impl Iterator<Item = usize>) -> String {
+    let (min, max) = values.fold((usize::MAX, 0), |(min, max), v| {
+        (std::cmp::min(min, v), std::cmp::max(max, v))
+    });
+    if min >= max {
+        max.to_string()
+    } else {
+        format!("{min}-{max}")
+    }
+}
+
+pub fn is_rotation(mv: Move) -> bool {
+    matches!(mv, Move::X(_) | Move::Y(_) | Move::Z(_))
+}
+
 pub fn display_move(mv: Move) -> String {
     match mv {
         Move::U(v) => "U".to_string() + display_move_variant(v),
@@ -393,3 +1161,124 @@ pub fn display_move_variant(v: MoveVariant) -> &'static str {
         MoveVariant::Inverse => "'",
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Move::{F, R, U, X};
+    use MoveVariant::*;
+
+    #[test]
+    fn test_simplify_cancels_reorient_with_rotation() {
+        let moves = [R(Standard), X(Standard), U(Standard)];
+        let solution = vec![Reorient::None, Reorient::L];
+        assert_eq!(
+            simplify(&moves, &[0, 0], &solution),
+            (
+                (-1, -1),
+                vec![Step::Move(R(Standard)), Step::Move(U(Standard))]
+            ),
+        );
+    }
+
+    #[test]
+    fn test_simplify_merges_reorient_with_rotation() {
+        let moves = [R(Standard), X(Standard), U(Standard)];
+        let solution = vec![Reorient::None, Reorient::U];
+        assert_eq!(
+            simplify(&moves, &[0, 1], &solution),
+            (
+                (0, 1),
+                vec![
+                    Step::Move(R(Standard)),
+                    Step::Reorient(Reorient::UFR),
+                    Step::Move(U(Standard)),
+                ],
+            ),
+        );
+    }
+
+    #[test]
+    fn test_simplify_merges_consecutive_reorients() {
+        let moves = [R(Standard), X(Standard), U(Standard)];
+        let solution = vec![Reorient::R, Reorient::R];
+        assert_eq!(
+            simplify(&moves, &[0, 0], &solution),
+            (
+                (0, 0),
+                vec![
+                    Step::Move(R(Standard)),
+                    Step::Reorient(Reorient::L),
+                    Step::Move(U(Standard)),
+                ],
+            ),
+        );
+    }
+
+    #[test]
+    fn test_simplify_keeps_non_adjacent_rotations() {
+        let moves = [X(Standard), R(Standard), U(Standard)];
+        let solution = vec![Reorient::None, Reorient::R];
+        assert_eq!(
+            simplify(&moves, &[0, 0], &solution),
+            (
+                (1, 1),
+                vec![
+                    Step::Move(X(Standard)),
+                    Step::Move(R(Standard)),
+                    Step::Reorient(Reorient::R),
+                    Step::Move(U(Standard)),
+                ],
+            ),
+        );
+    }
+
+    #[test]
+    fn test_parse_seconds() {
+        assert_eq!(parse_seconds("1.5"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_seconds("0"), Ok(Duration::ZERO));
+        assert!(parse_seconds("-1").is_err());
+        assert!(parse_seconds("NaN").is_err());
+        assert!(parse_seconds("inf").is_err());
+        assert!(parse_seconds("1e300").is_err());
+        assert!(parse_seconds("soon").is_err());
+    }
+
+    #[test]
+    fn test_parse_annotated_alg() {
+        assert_eq!(
+            parse_annotated_alg("R U R' U'"),
+            Ok((
+                vec![R(Standard), U(Standard), R(Inverse), U(Inverse)],
+                vec![0, 0, 0],
+            )),
+        );
+        assert_eq!(
+            parse_annotated_alg("R U *R' **U' F2"),
+            Ok((
+                vec![R(Standard), U(Standard), R(Inverse), U(Inverse), F(Double),],
+                vec![0, 1, 2, 0],
+            )),
+        );
+        assert_eq!(parse_annotated_alg("R"), Ok((vec![R(Standard)], vec![])));
+        assert_eq!(parse_annotated_alg(""), Ok((vec![], vec![])));
+    }
+
+    #[test]
+    fn test_parse_annotated_alg_rejects_unsupported_moves() {
+        assert!(parse_annotated_alg("R U2' Rw Fw' x2 y'").is_ok());
+        assert!(parse_annotated_alg("R 2Fw' U").is_err());
+        assert!(parse_annotated_alg("(R U R')").is_err());
+        assert!(parse_annotated_alg("R M U").is_err());
+        assert!(parse_annotated_alg("R 3Rw").is_err());
+        assert!(parse_annotated_alg("R r").is_err());
+        assert!(parse_annotated_alg("R U2'2").is_err());
+    }
+
+    #[test]
+    fn test_parse_annotated_alg_rejects_misplaced_stars() {
+        assert!(parse_annotated_alg("*R U").is_err());
+        assert!(parse_annotated_alg("R * U").is_err());
+        assert!(parse_annotated_alg("R U **").is_err());
+    }
+}
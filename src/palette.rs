@@ -0,0 +1,172 @@
+//! Syntax highlighting of terminal output.
+
+use clap::ValueEnum;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicU8, Ordering::SeqCst};
+
+static PALETTE: AtomicU8 = AtomicU8::new(Palette::None as u8);
+
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Palette {
+    /// No colors.
+    None = 0,
+    Default = 1,
+    /// Bold, bright colors for low-contrast displays. Moves and progress use
+    /// the terminal's own foreground color, so they stay readable on any
+    /// background.
+    HighContrast = 2,
+    /// Colors distinguishable with any common form of color blindness
+    /// (Okabe-Ito).
+    Colorblind = 3,
+}
+
+/// Kind of text to highlight.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Role {
+    Move,
+    Reorient,
+    /// Summary of search results.
+    Summary,
+    /// Search failures.
+    Warning,
+    /// Progress and statistics.
+    Info,
+    /// Comments in reconstruction files.
+    Comment,
+    /// Cost added to a phase of a reconstruction file.
+    Delta,
+}
+
+impl Palette {
+    /// Returns the SGR parameters for `role`, or `""` for unstyled text.
+    fn sgr(self, role: Role) -> &'static str {
+        use Role::*;
+
+        match self {
+            Palette::None => "",
+            Palette::Default => match role {
+                Move => "",
+                Reorient => "36",
+                Summary => "32",
+                Warning => "33",
+                Info => "2",
+                Comment => "2",
+                Delta => "35",
+            },
+            Palette::HighContrast => match role {
+                Move => "1",
+                Reorient => "1;93",
+                Summary => "1;92",
+                Warning => "1;91",
+                Info => "39",
+                Comment => "96",
+                Delta => "1;95",
+            },
+            Palette::Colorblind => match role {
+                Move => "",
+                Reorient => "38;5;32",
+                Summary => "38;5;36",
+                Warning => "38;5;208",
+                Info => "2",
+                Comment => "38;5;117",
+                Delta => "38;5;175",
+            },
+        }
+    }
+}
+
+/// Sets the palette used for all output. Colors are only used if standard
+/// output is a terminal and `NO_COLOR` is not set.
+pub fn set(palette: Palette) {
+    let enabled = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    let palette = if enabled { palette } else { Palette::None };
+    PALETTE.store(palette as u8, SeqCst);
+}
+
+fn get() -> Palette {
+    match PALETTE.load(SeqCst) {
+        1 => Palette::Default,
+        2 => Palette::HighContrast,
+        3 => Palette::Colorblind,
+        _ => Palette::None,
+    }
+}
+
+/// Highlights `text` according to the current palette.
+pub fn paint(role: Role, text: &str) -> String {
+    match get().sgr(role) {
+        "" => text.to_string(),
+        sgr => format!("\x1b[{sgr}m{text}\x1b[0m"),
+    }
+}
+
+/// Highlights an algorithm, distinguishing reorientations from moves.
+pub fn paint_alg(alg: &str) -> String {
+    alg.split(' ')
+        .map(|token| {
+            if token.starts_with('O') || token.starts_with("23I:") {
+                paint(Role::Reorient, token)
+            } else {
+                paint(Role::Move, token)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Highlights an optimized reconstruction file.
+pub fn paint_recon(text: &str) -> String {
+    let mut ret = String::new();
+    for line in text.lines() {
+        let (code, comment) = match line.split_once("//") {
+            Some((code, comment)) => (code, Some(comment)),
+            None => (line, None),
+        };
+        ret += &paint_alg(code);
+        if let Some(comment) = comment {
+            let (label, delta) = split_delta(comment);
+            ret += &paint(Role::Comment, &format!("//{label}"));
+            if let Some(delta) = delta {
+                ret += " ";
+                ret += &paint(Role::Delta, delta);
+            }
+        }
+        ret += "\n";
+    }
+    ret
+}
+
+/// Splits a comment into its label and the parenthesized cost that
+/// `recon::optimize` appends to each phase, if there is one.
+fn split_delta(comment: &str) -> (&str, Option<&str>) {
+    let start = comment
+        .rfind(" (+")
+        .filter(|_| comment.ends_with(" ETM)"))
+        .or_else(|| {
+            comment
+                .rfind(" (not optimized: ")
+                .filter(|_| comment.ends_with(')'))
+        });
+    match start {
+        Some(i) => (&comment[..i], Some(&comment[i + 1..])),
+        None => (comment, None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_delta_only_matches_recon_suffix() {
+        assert_eq!(split_delta(" OLL (2-look)"), (" OLL (2-look)", None));
+        assert_eq!(
+            split_delta(" OLL (2-look) (+1 reorients, +2 ETM)"),
+            (" OLL (2-look)", Some("(+1 reorients, +2 ETM)")),
+        );
+        assert_eq!(
+            split_delta(" (not optimized: out of budget)"),
+            ("", Some("(not optimized: out of budget)")),
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Optimization of whole reconstruction files.
+//!
+//! Consecutive lines containing moves form one phase, which is optimized as a
+//! single algorithm. A phase ends at a comment-only or blank line (e.g. a
+//! `// F2L` header), or at a move line with a trailing comment, which labels
+//! the phase (e.g. `R U R' U' // F2L 1`). Comment-only and blank lines are kept
+//! as-is.
+
+use crate::table::TranspositionTable;
+use crate::{
+    display_steps, iddfs, is_rotation, parse_annotated_alg, reorient_count, Args, Budget, Notation,
+    Step,
+};
+
+/// Part of a reconstruction file.
+#[derive(Debug, PartialEq, Eq)]
+enum Chunk<'a> {
+    /// Line that is copied unchanged.
+    Verbatim(&'a str),
+    /// Moves of one phase, one entry per line, with its label if any.
+    Phase {
+        lines: Vec<&'a str>,
+        label: Option<&'a str>,
+    },
+}
+
+/// Optimizes the reorients of each phase independently and returns the file
+/// with each phase replaced by its best execution, annotated with what it
+/// adds to the original. The optimized moves keep the line breaks of the
+/// original phase.
+pub fn optimize(text: &str, args: &Args) -> String {
+    let mut ret = String::new();
+    let mut total_reorients = 0;
+    let mut total_etm = 0;
+    let mut table = TranspositionTable::new(args.table_size);
+
+    for chunk in split_phases(text) {
+        let (lines, label) = match chunk {
+            Chunk::Verbatim(line) => {
+                ret += line;
+                ret += "\n";
+                continue;
+            }
+            Chunk::Phase { lines, label } => (lines, label),
+        };
+
+        let label = label.map(|c| format!("{c} ")).unwrap_or_default();
+        let unchanged = |reason: &str| {
+            let (last, rest) = lines.split_last().expect("phases are not empty");
+            let mut ret = String::new();
+            for line in rest {
+                ret += &format!("{}\n", line.trim());
+            }
+            ret + &format!("{} // {label}(not optimized: {reason})\n", last.trim())
+        };
+
+        let Ok((alg, preferences)) = parse_annotated_alg(&lines.join(" ")) else {
+            ret += &unchanged("unparseable");
+            continue;
+        };
+        let mut budget = Budget::new(args.node_limit, args.time_limit);
+        table.clear();
+        let (min_reorients, solutions) = iddfs(
+            &alg,
+            &preferences,
+            args.search_depth(),
+            None,
+            &mut budget,
+            &mut table,
+        );
+        let best = solutions.into_iter().min_by_key(|(cost, _steps)| *cost);
+
+        match best {
+            Some(((_weighted_cost, etm), steps)) => {
+                let lengths: Vec<usize> = lines.iter().map(|line| face_turns(line)).collect();
+                let split = split_steps(&steps, &lengths);
+                let (last, rest) = split.split_last().expect("phases are not empty");
+                for line in rest.iter().filter(|line| !line.is_empty()) {
+                    ret += &format!("{}\n", display_steps(line, Notation::global()));
+                }
+                let string = display_steps(last, Notation::global());
+                let reorient_count = reorient_count(&steps);
+                ret += &format!("{string} // {label}(+{reorient_count} reorients, {etm:+} ETM)\n");
+                total_reorients += reorient_count;
+                total_etm += etm;
+            }
+            None => {
+                let reason = if budget.is_exhausted() {
+                    format!("out of budget, needs at least {min_reorients} reorients")
+                } else {
+                    "no solutions".to_string()
+                };
+                ret += &unchanged(&reason);
+            }
+        }
+    }
+
+    ret += &format!("// Total: +{total_reorients} reorients, {total_etm:+} ETM\n");
+    ret
+}
+
+/// Splits a reconstruction file into phases and lines to copy unchanged.
+fn split_phases(text: &str) -> Vec<Chunk<'_>> {
+    let mut ret = vec![];
+    let mut lines = vec![];
+
+    for line in text.lines() {
+        let (code, comment) = match line.split_once("//") {
+            Some((code, comment)) => (code, Some(comment.trim())),
+            None => (line, None),
+        };
+        if code.trim().is_empty() {
+            if !lines.is_empty() {
+                let lines = std::mem::take(&mut lines);
+                ret.push(Chunk::Phase { lines, label: None });
+            }
+            ret.push(Chunk::Verbatim(line));
+            continue;
+        }
+
+        lines.push(code);
+        if let Some(label) = comment {
+            let lines = std::mem::take(&mut lines);
+            ret.push(Chunk::Phase {
+                lines,
+                label: Some(label),
+            });
+        }
+    }
+    if !lines.is_empty() {
+        ret.push(Chunk::Phase { lines, label: None });
+    }
+
+    ret
+}
+
+/// Returns the number of moves on a line that are not rotations, which are
+/// the moves kept in order by the search.
+fn face_turns(line: &str) -> usize {
+    line.split_whitespace()
+        .filter(|token| !token.trim_start_matches('*').starts_with(['x', 'y', 'z']))
+        .count()
+}
+
+/// Splits the steps of an optimized phase back into lines with `lengths` face
+/// turns each. Reorients and rotations between two lines start the next one.
+fn split_steps(steps: &[Step], lengths: &[usize]) -> Vec<Vec<Step>> {
+    let mut ret = vec![vec![]; lengths.len()];
+    let last = lengths.len() - 1;
+    let mut line = 0;
+    let mut turns = 0;
+
+    for &step in steps {
+        while line < last && turns == lengths[line] {
+            line += 1;
+            turns = 0;
+        }
+        if matches!(step, Step::Move(mv) if !is_rotation(mv)) {
+            turns += 1;
+        }
+        ret[line].push(step);
+    }
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Reorient;
+    use cubesim::{Move, MoveVariant::Standard};
+
+    #[test]
+    fn test_split_phases_groups_lines_under_headers() {
+        let text = "// F2L\ny R U' R'\nU R U R' // pair 1\nL' U L\n\n// OLL\nF R U R' U' F'";
+        assert_eq!(
+            split_phases(text),
+            vec![
+                Chunk::Verbatim("// F2L"),
+                Chunk::Phase {
+                    lines: vec!["y R U' R'", "U R U R' "],
+                    label: Some("pair 1"),
+                },
+                Chunk::Phase {
+                    lines: vec!["L' U L"],
+                    label: None,
+                },
+                Chunk::Verbatim(""),
+                Chunk::Verbatim("// OLL"),
+                Chunk::Phase {
+                    lines: vec!["F R U R' U' F'"],
+                    label: None,
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn test_face_turns_skips_rotations() {
+        assert_eq!(face_turns("y R *U' x2 R' *z"), 3);
+    }
+
+    #[test]
+    fn test_split_steps_keeps_line_breaks() {
+        let r = Step::Move(Move::R(Standard));
+        let u = Step::Move(Move::U(Standard));
+        let y = Step::Move(Move::Y(Standard));
+        let o = Step::Reorient(Reorient::U);
+        assert_eq!(
+            split_steps(&[y, r, u, o, r, u, r], &[2, 0, 3]),
+            vec![vec![y, r, u], vec![], vec![o, r, u, r]],
+        );
+    }
+}
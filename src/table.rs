@@ -0,0 +1,217 @@
+//! Bounded transposition table for memoizing search results.
+
+use cubesim::FaceletCube;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::Solution;
+
+/// Most solutions stored in one entry, so that the memory used by the table is
+/// bounded by its capacity. Larger results are cheap to recompute relative to
+/// their size anyway.
+pub const MAX_SOLUTIONS_PER_ENTRY: usize = 64;
+
+/// Fixed-size table of search results, keyed by state, number of moves left,
+/// and number of reorients left. Only valid for a single algorithm.
+///
+/// Each key maps to one slot. When two keys collide, the entry with more
+/// moves left (and therefore the more expensive subtree) is kept.
+pub struct TranspositionTable {
+    slots: Vec<Option<Entry>>,
+    occupied: usize,
+    pub stats: TableStats,
+}
+
+struct Entry {
+    state: FaceletCube,
+    moves_left: usize,
+    remaining_reorients: usize,
+    solutions: Vec<Solution>,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct TableStats {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries overwritten by a different entry at least as deep.
+    pub evictions: u64,
+    /// Entries not stored because their slot held a deeper entry.
+    pub rejections: u64,
+    /// Entries not stored because they had too many solutions.
+    pub oversized: u64,
+}
+
+impl TranspositionTable {
+    /// Creates a table with `capacity` slots. A capacity of 0 disables it.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: std::iter::repeat_with(|| None).take(capacity).collect(),
+            occupied: 0,
+            stats: TableStats::default(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Returns the number of occupied slots.
+    pub fn len(&self) -> usize {
+        self.occupied
+    }
+
+    /// Removes all entries and resets the stats, keeping the allocation, so
+    /// that the table can be reused for another algorithm.
+    pub fn clear(&mut self) {
+        if self.occupied > 0 {
+            self.slots.fill_with(|| None);
+            self.occupied = 0;
+        }
+        self.stats = TableStats::default();
+    }
+
+    fn index(&self, state: &FaceletCube, moves_left: usize, remaining_reorients: usize) -> usize {
+        let mut hasher = DefaultHasher::new();
+        (state, moves_left, remaining_reorients).hash(&mut hasher);
+        (hasher.finish() % self.slots.len() as u64) as usize
+    }
+
+    pub fn get(
+        &mut self,
+        state: &FaceletCube,
+        moves_left: usize,
+        remaining_reorients: usize,
+    ) -> Option<Vec<Solution>> {
+        if self.slots.is_empty() {
+            return None;
+        }
+
+        let index = self.index(state, moves_left, remaining_reorients);
+        match &self.slots[index] {
+            Some(entry)
+                if entry.moves_left == moves_left
+                    && entry.remaining_reorients == remaining_reorients
+                    && entry.state == *state =>
+            {
+                self.stats.hits += 1;
+                Some(entry.solutions.clone())
+            }
+            _ => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    pub fn insert(
+        &mut self,
+        state: &FaceletCube,
+        moves_left: usize,
+        remaining_reorients: usize,
+        solutions: &[Solution],
+    ) {
+        if self.slots.is_empty() {
+            return;
+        }
+        if solutions.len() > MAX_SOLUTIONS_PER_ENTRY {
+            self.stats.oversized += 1;
+            return;
+        }
+
+        let index = self.index(state, moves_left, remaining_reorients);
+        let slot = &mut self.slots[index];
+        match slot {
+            Some(existing) if existing.moves_left > moves_left => {
+                self.stats.rejections += 1;
+                return;
+            }
+            Some(_) => self.stats.evictions += 1,
+            None => self.occupied += 1,
+        }
+        *slot = Some(Entry {
+            state: state.clone(),
+            moves_left,
+            remaining_reorients,
+            solutions: solutions.to_vec(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Reorient;
+    use cubesim::{Cube, Move, MoveVariant};
+
+    fn states() -> (FaceletCube, FaceletCube) {
+        let solved = FaceletCube::new(3);
+        let turned = solved.apply_move(Move::R(MoveVariant::Standard));
+        (solved, turned)
+    }
+
+    #[test]
+    fn test_get_after_insert() {
+        let (solved, turned) = states();
+        let mut table = TranspositionTable::new(16);
+        let solutions = vec![vec![Reorient::R, Reorient::None]];
+        assert_eq!(table.get(&solved, 3, 1), None);
+        table.insert(&solved, 3, 1, &solutions);
+        assert_eq!(table.get(&solved, 3, 1), Some(solutions));
+        assert_eq!(table.get(&solved, 3, 2), None);
+        assert_eq!(table.get(&turned, 3, 1), None);
+        assert_eq!(table.stats.hits, 1);
+        assert_eq!(table.stats.misses, 3);
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn test_collisions_keep_deeper_entry() {
+        let (solved, turned) = states();
+        let mut table = TranspositionTable::new(1);
+        table.insert(&solved, 3, 1, &[]);
+        table.insert(&turned, 2, 1, &[]);
+        assert_eq!(table.stats.rejections, 1);
+        assert!(table.get(&solved, 3, 1).is_some());
+
+        table.insert(&turned, 3, 1, &[]);
+        assert_eq!(table.stats.evictions, 1);
+        assert!(table.get(&solved, 3, 1).is_none());
+        assert!(table.get(&turned, 3, 1).is_some());
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn test_oversized_entries_are_not_stored() {
+        let (solved, _) = states();
+        let mut table = TranspositionTable::new(16);
+        let solutions = vec![vec![Reorient::None]; MAX_SOLUTIONS_PER_ENTRY + 1];
+        table.insert(&solved, 3, 1, &solutions);
+        assert_eq!(table.stats.oversized, 1);
+        assert_eq!(table.len(), 0);
+        assert_eq!(table.get(&solved, 3, 1), None);
+    }
+
+    #[test]
+    fn test_zero_capacity_disables_table() {
+        let (solved, _) = states();
+        let mut table = TranspositionTable::new(0);
+        table.insert(&solved, 3, 1, &[]);
+        assert_eq!(table.get(&solved, 3, 1), None);
+        assert_eq!(table.len(), 0);
+        assert_eq!(table.stats, TableStats::default());
+    }
+
+    #[test]
+    fn test_clear_keeps_capacity() {
+        let (solved, turned) = states();
+        let mut table = TranspositionTable::new(16);
+        table.insert(&solved, 3, 1, &[]);
+        table.insert(&turned, 3, 1, &[]);
+        table.get(&solved, 3, 1);
+        table.clear();
+        assert_eq!(table.capacity(), 16);
+        assert_eq!(table.len(), 0);
+        assert_eq!(table.stats, TableStats::default());
+        assert_eq!(table.get(&solved, 3, 1), None);
+    }
+}
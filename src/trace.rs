@@ -0,0 +1,104 @@
+//! Recording and printing of the explored search tree, for tuning the
+//! heuristic and debugging the search.
+
+use cubesim::{Cube, FaceletCube};
+
+use crate::Reorient;
+
+/// Node in the explored DFS tree.
+#[derive(Debug, Clone)]
+pub struct TraceNode {
+    /// Reorientation applied just before this node.
+    pub reorient: Reorient,
+    /// Number of moves not yet applied.
+    pub moves_left: usize,
+    /// Reorientations still allowed at this node.
+    pub remaining_reorients: usize,
+    /// Pruning table lower bound for the state at this node.
+    pub lower_bound: i32,
+    /// Stickers of the state at this node.
+    pub state: String,
+    pub outcome: Outcome,
+    pub children: Vec<TraceNode>,
+    /// Number of nodes below this one that were too deep to record.
+    pub hidden_descendants: usize,
+}
+impl TraceNode {
+    pub fn new(state: &FaceletCube, moves_left: usize, remaining_reorients: usize) -> Self {
+        Self {
+            reorient: Reorient::None,
+            moves_left,
+            remaining_reorients,
+            lower_bound: crate::NAIVE_SOLVER.lower_bound(state),
+            state: state.state().iter().map(|face| face.to_string()).collect(),
+            outcome: Outcome::Expanded { solutions: 0 },
+            children: vec![],
+            hidden_descendants: 0,
+        }
+    }
+}
+
+/// Where the search records the nodes it explores.
+pub enum Trace<'a> {
+    /// Record nodes in full, along with `depth_left` more levels below them.
+    Record {
+        nodes: &'a mut Vec<TraceNode>,
+        depth_left: usize,
+    },
+    /// Only count nodes, because they are too deep to print.
+    Count(&'a mut usize),
+}
+
+/// What the search did at a node.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The remaining moves were applied and the result is solved.
+    Solved,
+    /// The remaining moves were applied and the result is not solved.
+    Unsolved { lower_bound: i32 },
+    /// The lower bound exceeds the number of moves left.
+    Pruned,
+    /// Every reorientation was tried from here.
+    Expanded { solutions: usize },
+    /// The result was found in the transposition table.
+    Cached { solutions: usize },
+}
+
+/// Prints the recorded tree, with a count in place of unrecorded subtrees.
+pub fn print_tree(roots: &[TraceNode]) {
+    for root in roots {
+        print_node(root, 0);
+    }
+}
+
+fn print_node(node: &TraceNode, depth: usize) {
+    let indent = "  ".repeat(depth);
+
+    let reorient = match node.reorient {
+        Reorient::None => "-".to_string(),
+        r => r.to_string().trim().to_string(),
+    };
+    let outcome = match node.outcome {
+        Outcome::Solved => "solved".to_string(),
+        Outcome::Unsolved { lower_bound } => {
+            format!("unsolved (end state lower bound {lower_bound})")
+        }
+        Outcome::Pruned => "pruned (lower bound exceeds moves left + 1)".to_string(),
+        Outcome::Expanded { solutions } => format!("expanded ({solutions} solutions)"),
+        Outcome::Cached { solutions } => format!("cached ({solutions} solutions)"),
+    };
+    println!(
+        "{indent}{reorient}: {} moves left, {} reorients left, lower bound {}: {outcome} [{}]",
+        node.moves_left, node.remaining_reorients, node.lower_bound, node.state,
+    );
+
+    for child in &node.children {
+        print_node(child, depth + 1);
+    }
+    if node.hidden_descendants > 0 {
+        println!(
+            "{indent}  ... {} more nodes (raise --inspect-depth to expand)",
+            node.hidden_descendants,
+        );
+    }
+}
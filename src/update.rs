@@ -0,0 +1,50 @@
+//! Opt-in check for newer releases on GitHub.
+
+use std::time::Duration;
+
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/Hypercubers/rocket/releases/latest";
+
+/// How long to wait for GitHub before giving up silently.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Release that is newer than the running build.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Update {
+    pub version: String,
+    pub changelog_url: String,
+}
+
+/// Queries GitHub for the latest release and returns it if it is newer than
+/// the running build. Any network or parsing failure is treated as "no
+/// update", because this check should never get in the way of using the
+/// program.
+pub fn check() -> Option<Update> {
+    let release: serde_json::Value = ureq::get(LATEST_RELEASE_URL)
+        .timeout(TIMEOUT)
+        .set("User-Agent", concat!("rocket/", env!("CARGO_PKG_VERSION")))
+        .call()
+        .ok()?
+        .into_json()
+        .ok()?;
+
+    let tag = release["tag_name"].as_str()?;
+    let changelog_url = release["html_url"].as_str()?;
+
+    let latest = parse_version(tag)?;
+    let current = parse_version(env!("CARGO_PKG_VERSION"))?;
+    (latest > current).then(|| Update {
+        version: tag.trim_start_matches('v').to_string(),
+        changelog_url: changelog_url.to_string(),
+    })
+}
+
+/// Parses a version string such as `v1.2.3` into its numeric components,
+/// ignoring any pre-release or build suffix.
+fn parse_version(s: &str) -> Option<Vec<u64>> {
+    s.trim_start_matches('v')
+        .split(['-', '+'])
+        .next()?
+        .split('.')
+        .map(|n| n.parse().ok())
+        .collect()
+}